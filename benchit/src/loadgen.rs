@@ -0,0 +1,172 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+use tokio::time::{delay_for, Duration};
+
+/// Full latency breakdown for a single run, replacing the single `wrk`
+/// average with real percentiles pulled out of an `hdrhistogram`.
+#[derive(Default)]
+pub struct LatencyStats {
+    pub p50: f32,
+    pub p90: f32,
+    pub p99: f32,
+    pub p999: f32,
+    pub max: f32,
+    pub rps: usize,
+}
+
+/// Arrival pattern for requests during a run.
+///
+/// `Closed` is the traditional "one-in-one-out" model: each worker waits for
+/// its previous response before issuing the next request, optionally pausing
+/// `delay` (think-time) in between. `Open` instead fires requests on a fixed
+/// schedule regardless of how many are still in flight, which is what
+/// exposes queueing once the target saturates.
+pub enum Workload {
+    Closed { delay: Duration },
+    Open { rate: u32 },
+}
+
+/// Describes one load run: how many workers, how they ramp up, how long the
+/// measurement window is, and what arrival pattern drives them.
+pub struct LoadProfile {
+    pub concurrency: u16,
+    pub ramp_up: Duration,
+    pub duration: Duration,
+    pub workload: Workload,
+}
+
+impl LoadProfile {
+    /// Short label used to tag `Results` rows, e.g. `closed` or `open@200rps`.
+    pub fn label(&self) -> String {
+        match self.workload {
+            Workload::Closed { delay } if delay.as_millis() > 0 => {
+                format!("closed+{}ms", delay.as_millis())
+            }
+            Workload::Closed { .. } => "closed".to_string(),
+            Workload::Open { rate } => format!("open@{}rps", rate),
+        }
+    }
+}
+
+fn micros_to_ms(v: u64) -> f32 {
+    v as f32 / 1000f32
+}
+
+/// Upper bound (in microseconds) for the latency histograms in this module
+/// and in `ws.rs`. A response slower than this is clamped to it rather than
+/// dropped, so a saturated target under `Workload::Open` doesn't silently
+/// lose its slowest (and most informative) latencies from the percentiles
+/// while `completed`/`rps` keeps counting it — the same lesson `monitor.rs`
+/// applies to its CPU histogram.
+pub(crate) const MAX_LATENCY_MICROS: u64 = 60_000_000;
+
+/// Drives `url` according to `profile`, recording every request's round-trip
+/// time (in microseconds) into a shared histogram. Requests that complete
+/// before the ramp-up window ends are not counted towards the measurement.
+///
+/// This replaces shelling out to `wrk`: no external binary on PATH, and the
+/// full latency distribution instead of a single scraped average.
+pub async fn run(url: String, profile: LoadProfile) -> anyhow::Result<LatencyStats> {
+    let histogram: Arc<Mutex<Histogram<u64>>> = Arc::new(Mutex::new(Histogram::new_with_bounds(
+        1,
+        MAX_LATENCY_MICROS,
+        3,
+    )?));
+    let client = reqwest::Client::new();
+    let run_start = Instant::now();
+    let measure_start = run_start + profile.ramp_up;
+    let deadline = measure_start + profile.duration;
+    let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let record = |histogram: &Mutex<Histogram<u64>>, elapsed_us: u64| {
+        if Instant::now() >= measure_start {
+            let clamped = elapsed_us.max(1).min(MAX_LATENCY_MICROS);
+            histogram.lock().unwrap().record(clamped).ok();
+            true
+        } else {
+            false
+        }
+    };
+
+    match profile.workload {
+        Workload::Closed { delay } => {
+            let mut workers = Vec::with_capacity(profile.concurrency as usize);
+            let stagger = profile
+                .ramp_up
+                .checked_div(profile.concurrency.max(1) as u32)
+                .unwrap_or_default();
+
+            for i in 0..profile.concurrency {
+                let client = client.clone();
+                let url = url.clone();
+                let histogram = histogram.clone();
+                let completed = completed.clone();
+                workers.push(tokio::spawn(async move {
+                    delay_for(stagger * i as u32).await;
+                    while Instant::now() < deadline {
+                        let start = Instant::now();
+                        if client.get(&url).send().await.is_ok() {
+                            let elapsed_us = start.elapsed().as_micros() as u64;
+                            if record(&histogram, elapsed_us) {
+                                completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            }
+                        }
+                        if delay.as_millis() > 0 {
+                            delay_for(delay).await;
+                        }
+                    }
+                }));
+            }
+            for worker in workers {
+                worker.await?;
+            }
+        }
+        Workload::Open { rate } => {
+            let mut tasks = Vec::new();
+            while Instant::now() < deadline {
+                // Ramp the fire rate linearly from 0 up to `rate` over
+                // `profile.ramp_up`, the same way the closed model staggers
+                // worker startup, instead of slamming the target at the
+                // full rate from t=0.
+                let elapsed = Instant::now().saturating_duration_since(run_start);
+                let current_rate = if profile.ramp_up == Duration::default() || elapsed >= profile.ramp_up {
+                    rate.max(1) as f64
+                } else {
+                    (rate as f64 * elapsed.as_secs_f64() / profile.ramp_up.as_secs_f64()).max(1f64)
+                };
+                let period = Duration::from_secs_f64(1f64 / current_rate);
+
+                let client = client.clone();
+                let url = url.clone();
+                let histogram = histogram.clone();
+                let completed = completed.clone();
+                tasks.push(tokio::spawn(async move {
+                    let start = Instant::now();
+                    if client.get(&url).send().await.is_ok() {
+                        let elapsed_us = start.elapsed().as_micros() as u64;
+                        if record(&histogram, elapsed_us) {
+                            completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    }
+                }));
+                delay_for(period).await;
+            }
+            for task in tasks {
+                task.await?;
+            }
+        }
+    }
+
+    let elapsed = profile.duration.as_secs_f32().max(f32::EPSILON);
+    let histogram = histogram.lock().unwrap();
+    Ok(LatencyStats {
+        p50: micros_to_ms(histogram.value_at_quantile(0.50)),
+        p90: micros_to_ms(histogram.value_at_quantile(0.90)),
+        p99: micros_to_ms(histogram.value_at_quantile(0.99)),
+        p999: micros_to_ms(histogram.value_at_quantile(0.999)),
+        max: micros_to_ms(histogram.max()),
+        rps: (completed.load(std::sync::atomic::Ordering::Relaxed) as f32 / elapsed) as usize,
+    })
+}
@@ -2,7 +2,20 @@ use structopt::StructOpt;
 use tokio::process::Command;
 use std::process::Stdio;
 use tokio::time::{delay_for, Duration};
-use psutil::process::processes;
+
+mod loadgen;
+use loadgen::{LatencyStats, LoadProfile, Workload};
+
+mod metrics;
+use metrics::Metrics;
+use std::sync::Arc;
+
+mod monitor;
+use monitor::{monitor_processes, ProcessesReport};
+
+mod ws;
+
+mod output;
 
 /// Automation for running load tests and gathering stats
 /// It uses wrk under the hood, make sure to have it in the PATH
@@ -26,6 +39,39 @@ struct Opt {
     /// Measurement time in seconds
     #[structopt(short="t", long="time", default_value="60")]
     time: u16,
+    /// Fall back to shelling out to `wrk` instead of the built-in load generator
+    #[structopt(long="use-wrk")]
+    use_wrk: bool,
+    /// Linearly bring workers up to each step's concurrency (or, with --rate, the fire rate up to --rate) over this many seconds before measuring
+    #[structopt(long="ramp-up", default_value="0")]
+    ramp_up: u16,
+    /// Think-time each worker waits between requests, in milliseconds (closed model only)
+    #[structopt(long="delay", default_value="0")]
+    delay: u16,
+    /// Open-model load: fire requests on a fixed schedule at this rate (req/s) instead of the closed-loop default
+    #[structopt(long="rate")]
+    rate: Option<u32>,
+    /// Serve Prometheus text-format metrics at /metrics on this port for the duration of the run
+    #[structopt(long="prometheus-port")]
+    prometheus_port: Option<u16>,
+    /// Push the final metrics registry to a Prometheus Pushgateway URL once the run completes
+    #[structopt(long="push-gateway")]
+    push_gateway: Option<String>,
+    /// Run a WebSocket load test against this path instead of the HTTP /tasks benchmark
+    #[structopt(long="ws")]
+    ws: Option<String>,
+    /// Payload size per WebSocket frame, in KB (--ws mode only)
+    #[structopt(long="ws-size", default_value="1")]
+    ws_size: usize,
+    /// Reconnect a WebSocket worker after sending this many bytes (--ws mode only)
+    #[structopt(long="max-payload")]
+    max_payload: Option<u64>,
+    /// Emit machine-readable results alongside the printed table (json, ndjson or csv)
+    #[structopt(long="output")]
+    output: Option<output::Format>,
+    /// Write --output results to this file instead of stdout
+    #[structopt(long="output-file")]
+    output_file: Option<String>,
 }
 
 fn wrk(concurrency: u16, url: &String, delay: u16) -> Command {
@@ -39,52 +85,28 @@ fn wrk(concurrency: u16, url: &String, delay: u16) -> Command {
     wrk
 }
 
-#[derive(Default)]
-struct ProcessesReport {
-    postgres: ProcessReport,
-    node: ProcessReport,
-    actix: ProcessReport,
-}
-
-#[derive(Default)]
-struct ProcessReport {
-    cpu: f32,
-    mem: u64,
-}
-
-async fn monitor_processes() -> anyhow::Result<ProcessesReport> {
-    let procs: Vec<_> = processes()?
-        .into_iter()
-        .filter_map(|p| p.ok())
-        .collect();
-
-    delay_for(Duration::from_secs(5)).await;
-
-    let proc_stats = |name| procs.iter()
-            .filter(|p| p.name().is_ok() && p.name().unwrap().contains(name))
-            .cloned()
-            .fold(
-                ProcessReport::default(), 
-                |mut acc, mut p| {
-                    acc.cpu += p.cpu_percent().unwrap();
-                    acc.mem += p.memory_info().unwrap().rss();
-                    acc
-                }
-            );
-
-    Ok(ProcessesReport {
-        postgres: proc_stats("postgres"),
-        node: proc_stats("node"),
-        actix: proc_stats("actix"),
-    })
-}
-
 #[derive(Default)]
 struct WrkStats {
     latency: f32,
     rps: usize,
 }
 
+impl From<WrkStats> for LatencyStats {
+    /// `wrk` only reports a single average latency, so every percentile
+    /// collapses onto it; this just keeps the `--use-wrk` fallback slotting
+    /// into the same `Results`/`print_charts` machinery as the real histogram.
+    fn from(wrk: WrkStats) -> Self {
+        LatencyStats {
+            p50: wrk.latency,
+            p90: wrk.latency,
+            p99: wrk.latency,
+            p999: wrk.latency,
+            max: wrk.latency,
+            rps: wrk.rps,
+        }
+    }
+}
+
 fn process_wrk(out: Vec<u8>) -> anyhow::Result<WrkStats> {
     let stdout = String::from_utf8(out)?;
     let latency_re = regex::Regex::new(r"Latency\s+(\d+\.\d+)(\w+)")?;
@@ -111,8 +133,9 @@ struct Results {
     test: String,
     name: String,
     concurrency: u16,
+    profile: String,
     proc_stats: ProcessesReport,
-    wrk_stats: WrkStats,
+    stats: LatencyStats,
 }
 
 use itertools::Itertools;
@@ -123,17 +146,17 @@ fn bars(n: usize) -> String {
 }
 
 fn print_charts(data: &Vec<Results>, width: usize) {
-    let (max_lat, max_rps) = data.iter().fold((0f32, 0), |acc, res| 
-        ( acc.0.max(res.wrk_stats.latency),
-        max(acc.1, res.wrk_stats.rps) ));
+    let (max_lat, max_rps) = data.iter().fold((0f32, 0), |acc, res|
+        ( acc.0.max(res.stats.p99),
+        max(acc.1, res.stats.rps) ));
 
-    println!("\nLatency in ms (lower is better)");
+    println!("\np99 latency in ms (lower is better)");
     for ((_test, conc), results) in &data.into_iter().group_by(|r| (&r.test, r.concurrency)) {
         println!("\nconcurrent load {}", conc);
         let results: Vec<_> = results.collect();
         for result in &results {
-            let size = (result.wrk_stats.latency * width as f32 / max_lat) as usize + 1;
-            println!("{:6} |{:width$}|", result.name, bars(size), width = width+2);
+            let size = (result.stats.p99 * width as f32 / max_lat) as usize + 1;
+            println!("{:6} [{}] |{:width$}|", result.name, result.profile, bars(size), width = width+2);
         }
     }
 
@@ -142,65 +165,204 @@ fn print_charts(data: &Vec<Results>, width: usize) {
         println!("\nconcurrent load {}", conc);
         let results: Vec<_> = results.collect();
         for result in &results {
-            let size = (result.wrk_stats.rps * width / max_rps) as usize + 1;
-            println!("{:6} |{:width$}|", result.name, bars(size), width = width+2);
+            let size = (result.stats.rps * width / max_rps) as usize + 1;
+            println!("{:6} [{}] |{:width$}|", result.name, result.profile, bars(size), width = width+2);
         }
-    }  
+    }
 }
 
-#[tokio::main(core_threads = 1)]
+// Multi-threaded by default: the in-process load generator now drives
+// `max_concurrency` async workers itself (no more shelling out to `wrk`),
+// and `--monitor` walks /proc synchronously every 200ms on top of that. A
+// single core_thread serialized all of it onto the thread whose scheduling
+// delays this harness is trying to measure, not just generate load with.
+#[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let opt = Opt::from_args();
-    let node_url = format!("http://{}:{}/tasks", opt.host, opt.node_port);
-    let actix_url = format!("http://{}:{}/tasks", opt.host, opt.actix_port);
+    let (scheme, path) = match &opt.ws {
+        Some(path) => ("ws", path.as_str()),
+        None => ("http", "/tasks"),
+    };
+    let node_url = format!("{}://{}:{}{}", scheme, opt.host, opt.node_port, path);
+    let actix_url = format!("{}://{}:{}{}", scheme, opt.host, opt.actix_port, path);
     let mut results: Vec<Results> = Vec::new();
 
+    let metrics = Arc::new(Metrics::new());
+    let mut metrics_server = None;
+    if let Some(port) = opt.prometheus_port {
+        let (stop_tx, stop_rx) = tokio::sync::oneshot::channel::<()>();
+        let metrics = metrics.clone();
+        let server = tokio::spawn(async move {
+            metrics::serve(metrics, port, async { stop_rx.await.ok(); }).await
+        });
+        metrics_server = Some((server, stop_tx));
+    }
+
+    let mut writer = match opt.output {
+        Some(format) => Some(output::Writer::new(
+            format,
+            opt.output_file.as_deref(),
+            output::RunMetadata::gather(
+                opt.max_concurrency,
+                opt.time,
+                opt.ramp_up,
+                opt.delay,
+                opt.rate,
+                opt.use_wrk,
+                opt.ws.clone(),
+            ),
+        )?),
+        None => None,
+    };
+
     // table header
     println!("Target,\tConcur,\tPG cpu,\tmem,\tND cpu,\tmem,\tAX cpu,\tmem,\tlat ms,\trps");
 
-    for test in &["", "?summary=wherever&full=true&limit=10"] {
+    let tests: Vec<&str> = if opt.ws.is_some() {
+        vec!["ws"]
+    } else {
+        vec!["", "?summary=wherever&full=true&limit=10"]
+    };
+
+    for test in &tests {
         println!("Starting test /tasks{}", test);
-        let url = |base: &String| format!("{}{}", base, test);
+        let url = |base: &String| if opt.ws.is_some() { base.clone() } else { format!("{}{}", base, test) };
         let mut c = 1u16;
 
-        while c < opt.max_concurrency {
+        loop {
             println!("concurrent load = {}", c);
+            let profile_label = if opt.ws.is_some() {
+                "ws".to_string()
+            } else if opt.use_wrk {
+                "wrk".to_string()
+            } else {
+                LoadProfile {
+                    concurrency: c,
+                    ramp_up: Duration::from_secs(opt.ramp_up as u64),
+                    duration: Duration::from_secs(opt.time as u64),
+                    workload: match opt.rate {
+                        Some(rate) => Workload::Open { rate },
+                        None => Workload::Closed { delay: Duration::from_millis(opt.delay as u64) },
+                    },
+                }.label()
+            };
             for sol in &[("node", url(&node_url)), ("actix", url(&actix_url))] {
-                let wrk = wrk(c, &sol.1, opt.time).output();
+                let stats = if opt.ws.is_some() {
+                    let url = sol.1.clone();
+                    let load = tokio::spawn(ws::run(
+                        url,
+                        c,
+                        Duration::from_secs(opt.time as u64),
+                        opt.ws_size * 1024,
+                        opt.max_payload,
+                    ));
+
+                    // ws::run has no ramp-up phase of its own: it measures
+                    // for the full opt.time starting at t=0, so the poller
+                    // must start immediately to cover the same window.
+                    let proc_stats = if opt.monitor {
+                            monitor_processes(Duration::from_secs(opt.time as u64)).await?
+                        } else {
+                            ProcessesReport::default()
+                        };
+
+                    (load.await??, proc_stats)
+                } else if opt.use_wrk {
+                    let wrk = wrk(c, &sol.1, opt.time).output();
 
-                let proc_stats = if opt.monitor {
-                        delay_for(Duration::from_secs(opt.time as u64 / 2)).await;
-                        monitor_processes().await?
-                    } else {
-                        ProcessesReport::default()
+                    // wrk is an external process already running for
+                    // opt.time the instant .output() is called above, with
+                    // no ramp-up concept, so the poller must start right
+                    // away to line up with its measured window.
+                    let proc_stats = if opt.monitor {
+                            monitor_processes(Duration::from_secs(opt.time as u64)).await?
+                        } else {
+                            ProcessesReport::default()
+                        };
+
+                    (process_wrk(wrk.await?.stdout)?.into(), proc_stats)
+                } else {
+                    let url = sol.1.clone();
+                    let workload = match opt.rate {
+                        Some(rate) => Workload::Open { rate },
+                        None => Workload::Closed { delay: Duration::from_millis(opt.delay as u64) },
+                    };
+                    let profile = LoadProfile {
+                        concurrency: c,
+                        ramp_up: Duration::from_secs(opt.ramp_up as u64),
+                        duration: Duration::from_secs(opt.time as u64),
+                        workload,
                     };
+                    let load = tokio::spawn(loadgen::run(url, profile));
 
-                let wrk_stats = process_wrk(wrk.await?.stdout)?;
-
-                println!("{:5},\t{},\t{:.2},\t{:3},\t{:.2},\t{:3},\t{:.2},\t{:3},\t{:.2},\t{}", 
-                    sol.0, c, 
-                    proc_stats.postgres.cpu / 100f32,
-                    proc_stats.postgres.mem / 1024 / 1024,
-                    proc_stats.node.cpu / 100f32,
-                    proc_stats.node.mem / 1024 / 1024,
-                    proc_stats.actix.cpu / 100f32,
-                    proc_stats.actix.mem / 1024 / 1024,
-                    wrk_stats.latency,
-                    wrk_stats.rps
-                );
+                    let proc_stats = if opt.monitor {
+                            // The load itself only starts measuring after its
+                            // own ramp-up, so the poller has to wait out the
+                            // same ramp before its window lines up with
+                            // [ramp_up, ramp_up+time] instead of sampling the
+                            // warm-up and finishing early.
+                            delay_for(Duration::from_secs(opt.ramp_up as u64)).await;
+                            monitor_processes(Duration::from_secs(opt.time as u64)).await?
+                        } else {
+                            ProcessesReport::default()
+                        };
 
-                results.push(
-                    Results { 
-                        test: test.to_string(), name: sol.0.to_string(), concurrency: c, proc_stats, wrk_stats 
-                    }
+                    (load.await??, proc_stats)
+                };
+                let (stats, proc_stats): (LatencyStats, ProcessesReport) = stats;
+
+                println!("{:5},\t{},\t{:.2},\t{:3},\t{:.2},\t{:3},\t{:.2},\t{:3},\t{:.2},\t{}",
+                    sol.0, c,
+                    proc_stats.postgres.cpu_p50 / 100f32,
+                    proc_stats.postgres.max_rss / 1024 / 1024,
+                    proc_stats.node.cpu_p50 / 100f32,
+                    proc_stats.node.max_rss / 1024 / 1024,
+                    proc_stats.actix.cpu_p50 / 100f32,
+                    proc_stats.actix.max_rss / 1024 / 1024,
+                    stats.p99,
+                    stats.rps
                 );
+
+                let result = Results {
+                    test: test.to_string(), name: sol.0.to_string(), concurrency: c,
+                    profile: profile_label.clone(), proc_stats, stats
+                };
+                metrics.record(&result);
+                if let Some(writer) = writer.as_mut() {
+                    writer.write_result(&result)?;
+                }
+                results.push(result);
+            }
+
+            if opt.rate.is_some() {
+                // Open-model runs are driven entirely by --rate; concurrency
+                // never enters the firing schedule, so sweeping it would
+                // just repeat the identical fixed-rate test at every step.
+                break;
             }
             c *= 2;
+            if c >= opt.max_concurrency {
+                break;
+            }
         }
 
         print_charts(&results, 100);
         results.truncate(0);
     }
-    Ok(())
 
+    if let Some(gateway_url) = &opt.push_gateway {
+        metrics::push(&metrics, gateway_url).await?;
+    }
+    if let Some((server, stop_tx)) = metrics_server {
+        stop_tx.send(()).ok();
+        server.await??;
+    }
+
+    println!("\nbenchit runner peak RSS: {} MB", monitor::own_max_rss_bytes() / 1024 / 1024);
+
+    if let Some(writer) = writer {
+        writer.finish()?;
+    }
+
+    Ok(())
 }
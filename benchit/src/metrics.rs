@@ -0,0 +1,144 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Response, Server};
+use prometheus_client::encoding::text::encode;
+use prometheus_client::encoding::{EncodeLabelSet, EncodeLabelValue};
+use prometheus_client::metrics::family::Family;
+use prometheus_client::metrics::gauge::Gauge;
+use prometheus_client::registry::Registry;
+
+/// `bench_latency_ms` and `bench_proc_cpu` are fractional (sub-millisecond
+/// p99s, CPU usage below 100%), so they need a float gauge — the default
+/// `Gauge<i64, AtomicI64>` truncates both to zero far too often.
+type FloatGauge = Gauge<f64, AtomicU64>;
+
+use crate::Results;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+struct Labels {
+    target: Target,
+    test: String,
+    concurrency: u16,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelValue)]
+enum Target {
+    Node,
+    Actix,
+    Postgres,
+}
+
+/// Registry of gauges fed from each completed `Results` row, so a long sweep
+/// can be scraped or dashboarded instead of only printed at the end.
+pub struct Metrics {
+    registry: Mutex<Registry>,
+    rps: Family<Labels, Gauge>,
+    latency_p99_ms: Family<Labels, FloatGauge>,
+    proc_cpu: Family<Labels, FloatGauge>,
+    proc_mem_bytes: Family<Labels, Gauge>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::default();
+        let rps = Family::default();
+        let latency_p99_ms = Family::default();
+        let proc_cpu = Family::default();
+        let proc_mem_bytes = Family::default();
+
+        registry.register("bench_rps", "Requests per second", rps.clone());
+        registry.register("bench_latency_ms", "p99 latency in milliseconds", latency_p99_ms.clone());
+        registry.register("bench_proc_cpu", "Target process CPU usage (0-1 per core)", proc_cpu.clone());
+        registry.register("bench_proc_mem_bytes", "Target process resident memory in bytes", proc_mem_bytes.clone());
+
+        Metrics { registry: Mutex::new(registry), rps, latency_p99_ms, proc_cpu, proc_mem_bytes }
+    }
+
+    /// Populates the gauges for one `Results` row (one target at one concurrency level).
+    pub fn record(&self, result: &Results) {
+        let target = match result.name.as_str() {
+            "node" => Target::Node,
+            _ => Target::Actix,
+        };
+        let labels = Labels {
+            target: target.clone(),
+            test: result.test.clone(),
+            concurrency: result.concurrency,
+        };
+
+        self.rps.get_or_create(&labels).set(result.stats.rps as i64);
+        self.latency_p99_ms.get_or_create(&labels).set(result.stats.p99 as f64);
+
+        let (cpu, mem) = match target {
+            Target::Node => (result.proc_stats.node.cpu_p50, result.proc_stats.node.max_rss),
+            Target::Actix => (result.proc_stats.actix.cpu_p50, result.proc_stats.actix.max_rss),
+            Target::Postgres => unreachable!("target is derived from result.name, never postgres"),
+        };
+        self.proc_cpu.get_or_create(&labels).set((cpu / 100f32) as f64);
+        self.proc_mem_bytes.get_or_create(&labels).set(mem as i64);
+
+        // Postgres isn't an HTTP target so it has no rps/latency series, but
+        // it's the shared resource both node and actix contend on, so it
+        // still needs its own proc_cpu/proc_mem_bytes point.
+        let pg_labels = Labels { target: Target::Postgres, test: result.test.clone(), concurrency: result.concurrency };
+        self.proc_cpu.get_or_create(&pg_labels).set((result.proc_stats.postgres.cpu_p50 / 100f32) as f64);
+        self.proc_mem_bytes.get_or_create(&pg_labels).set(result.proc_stats.postgres.max_rss as i64);
+    }
+
+    fn encode(&self) -> anyhow::Result<String> {
+        let mut buf = String::new();
+        encode(&mut buf, &self.registry.lock().unwrap())?;
+        Ok(buf)
+    }
+}
+
+/// Serves the encoded registry on `GET http://0.0.0.0:<port>/metrics` until
+/// `stop` resolves; any other method or path gets a 404.
+pub async fn serve(
+    metrics: Arc<Metrics>,
+    port: u16,
+    stop: impl std::future::Future<Output = ()>,
+) -> anyhow::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let make_svc = make_service_fn(move |_conn| {
+        let metrics = metrics.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |req| {
+                let metrics = metrics.clone();
+                async move {
+                    if req.method() != hyper::Method::GET || req.uri().path() != "/metrics" {
+                        return Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(hyper::StatusCode::NOT_FOUND)
+                                .body(Body::empty())
+                                .unwrap(),
+                        );
+                    }
+                    let body = metrics.encode().unwrap_or_default();
+                    Ok::<_, Infallible>(Response::new(Body::from(body)))
+                }
+            }))
+        }
+    });
+
+    Server::bind(&addr)
+        .serve(make_svc)
+        .with_graceful_shutdown(stop)
+        .await?;
+    Ok(())
+}
+
+/// POSTs the final encoded registry once to a Prometheus Pushgateway.
+pub async fn push(metrics: &Metrics, gateway_url: &str) -> anyhow::Result<()> {
+    let body = metrics.encode()?;
+    reqwest::Client::new()
+        .post(gateway_url)
+        .body(body)
+        .send()
+        .await?;
+    Ok(())
+}
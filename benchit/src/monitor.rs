@@ -0,0 +1,143 @@
+use std::mem::MaybeUninit;
+use std::time::Instant;
+
+use hdrhistogram::Histogram;
+use psutil::process::{processes, Process};
+use tokio::time::{delay_for, Duration};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+#[derive(Default)]
+pub struct ProcessesReport {
+    pub postgres: ProcessReport,
+    pub node: ProcessReport,
+    pub actix: ProcessReport,
+}
+
+/// Peak RSS and CPU distribution for one matched process group, gathered
+/// over the whole measurement window rather than a single instantaneous read.
+#[derive(Default)]
+pub struct ProcessReport {
+    pub max_rss: u64,
+    pub cpu_p50: f32,
+    pub cpu_p99: f32,
+}
+
+// `sample_group` sums `cpu_percent()` across every matched process, so a
+// busy multi-process group (postgres backends, a multi-threaded node/actix)
+// routinely reports well over one core's worth of CPU. Size the histogram
+// for many cores instead of capping at 100%, so a saturated host doesn't
+// get silently dropped down to its few sub-100% readings.
+const MAX_CPU_CENTIPERCENT: u64 = 100 * 100 * 64;
+
+struct Tracker {
+    mem: Histogram<u64>,
+    cpu: Histogram<u64>,
+    max_rss: u64,
+}
+
+impl Tracker {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Tracker {
+            // exponential buckets from 1MiB up, 2 significant figures
+            mem: Histogram::new_with_bounds(1024 * 1024, 1u64 << 40, 2)?,
+            // cpu percent recorded in centi-percent, up to MAX_CPU_CENTIPERCENT
+            cpu: Histogram::new_with_bounds(1, MAX_CPU_CENTIPERCENT, 2)?,
+            max_rss: 0,
+        })
+    }
+
+    fn sample(&mut self, cpu_percent: f32, rss: u64) {
+        let centipercent = (cpu_percent * 100f32).max(0f32) as u64;
+        self.cpu.record(centipercent.min(MAX_CPU_CENTIPERCENT)).ok();
+        self.mem.record(rss.max(1)).ok();
+        self.max_rss = self.max_rss.max(rss);
+    }
+
+    fn report(&self) -> ProcessReport {
+        ProcessReport {
+            max_rss: self.max_rss,
+            cpu_p50: self.cpu.value_at_quantile(0.50) as f32 / 100f32,
+            cpu_p99: self.cpu.value_at_quantile(0.99) as f32 / 100f32,
+        }
+    }
+}
+
+fn matching(procs: &[Process], name: &str) -> Vec<Process> {
+    procs.iter()
+        .filter(|p| p.name().map(|n| n.contains(name)).unwrap_or(false))
+        .cloned()
+        .collect()
+}
+
+/// `cpu_percent()` is interval-based: it diffs busy time against the instant
+/// the handle was created (or last queried), so a freshly built handle has
+/// nothing to diff against and returns garbage on its first call. Prime each
+/// handle once up front so the first real sample in the poll loop has a
+/// genuine ~200ms window behind it.
+fn prime(procs: &mut [Process]) {
+    for p in procs {
+        p.cpu_percent().ok();
+    }
+}
+
+fn sample_group(procs: &mut [Process]) -> (f32, u64) {
+    procs.iter_mut().fold((0f32, 0u64), |mut acc, p| {
+        acc.0 += p.cpu_percent().unwrap_or(0f32);
+        acc.1 += p.memory_info().map(|m| m.rss()).unwrap_or(0);
+        acc
+    })
+}
+
+/// Polls processes matching "postgres"/"node"/"actix" by name every
+/// `POLL_INTERVAL` across the whole `duration`, tracking peak RSS and a CPU
+/// percentile distribution per group instead of a single snapshot taken
+/// after a fixed sleep.
+///
+/// The matched `Process` handles are built once and reused for every poll:
+/// `cpu_percent()` reports the delta since its previous call on the same
+/// handle, so rebuilding handles each tick would leave each sample measuring
+/// only the sliver of time since its handle was built.
+pub async fn monitor_processes(duration: Duration) -> anyhow::Result<ProcessesReport> {
+    let mut postgres = Tracker::new()?;
+    let mut node = Tracker::new()?;
+    let mut actix = Tracker::new()?;
+
+    let procs: Vec<_> = processes()?.into_iter().filter_map(|p| p.ok()).collect();
+    let mut postgres_procs = matching(&procs, "postgres");
+    let mut node_procs = matching(&procs, "node");
+    let mut actix_procs = matching(&procs, "actix");
+    prime(&mut postgres_procs);
+    prime(&mut node_procs);
+    prime(&mut actix_procs);
+
+    let deadline = Instant::now() + duration;
+    while Instant::now() < deadline {
+        delay_for(POLL_INTERVAL).await;
+
+        let (cpu, mem) = sample_group(&mut postgres_procs);
+        postgres.sample(cpu, mem);
+        let (cpu, mem) = sample_group(&mut node_procs);
+        node.sample(cpu, mem);
+        let (cpu, mem) = sample_group(&mut actix_procs);
+        actix.sample(cpu, mem);
+    }
+
+    Ok(ProcessesReport {
+        postgres: postgres.report(),
+        node: node.report(),
+        actix: actix.report(),
+    })
+}
+
+/// Peak resident set size of this benchmark runner itself, in bytes, via
+/// `getrusage(RUSAGE_SELF)`. Lets us see the overhead of the harness
+/// alongside the processes it's measuring.
+pub fn own_max_rss_bytes() -> u64 {
+    unsafe {
+        let mut usage = MaybeUninit::<libc::rusage>::zeroed();
+        libc::getrusage(libc::RUSAGE_SELF, usage.as_mut_ptr());
+        // Linux reports ru_maxrss in kilobytes.
+        usage.assume_init().ru_maxrss as u64 * 1024
+    }
+}
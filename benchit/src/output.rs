@@ -0,0 +1,203 @@
+use std::fs::File;
+use std::io::{stdout, Write};
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::Results;
+
+#[derive(Clone, Copy)]
+pub enum Format {
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "csv" => Ok(Format::Csv),
+            other => Err(anyhow::anyhow!("unknown output format '{}', expected json, ndjson or csv", other)),
+        }
+    }
+}
+
+/// Metadata stamped once at the top of a run's output so a stored baseline
+/// can be matched back to the build and settings that produced it.
+#[derive(Serialize)]
+pub struct RunMetadata {
+    pub host: String,
+    pub timestamp: String,
+    pub git_sha: Option<String>,
+    pub max_concurrency: u16,
+    pub time_secs: u16,
+    pub ramp_up_secs: u16,
+    pub delay_ms: u16,
+    pub rate: Option<u32>,
+    pub use_wrk: bool,
+    pub ws: Option<String>,
+}
+
+impl RunMetadata {
+    pub fn gather(
+        max_concurrency: u16,
+        time_secs: u16,
+        ramp_up_secs: u16,
+        delay_ms: u16,
+        rate: Option<u32>,
+        use_wrk: bool,
+        ws: Option<String>,
+    ) -> Self {
+        RunMetadata {
+            host: hostname::get()
+                .ok()
+                .and_then(|h| h.into_string().ok())
+                .unwrap_or_else(|| "unknown".to_string()),
+            timestamp: format!("{:?}", std::time::SystemTime::now()),
+            git_sha: git_sha(),
+            max_concurrency,
+            time_secs,
+            ramp_up_secs,
+            delay_ms,
+            rate,
+            use_wrk,
+            ws,
+        }
+    }
+}
+
+fn git_sha() -> Option<String> {
+    let out = std::process::Command::new("git")
+        .args(&["rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(out.stdout).ok()?.trim().to_string())
+}
+
+/// Flat, CSV/JSON-friendly view of one `Results` row.
+#[derive(Serialize)]
+struct ResultRow {
+    target: String,
+    test: String,
+    concurrency: u16,
+    profile: String,
+    p50_ms: f32,
+    p90_ms: f32,
+    p99_ms: f32,
+    p999_ms: f32,
+    max_ms: f32,
+    rps: usize,
+    postgres_cpu_p50: f32,
+    postgres_max_rss: u64,
+    node_cpu_p50: f32,
+    node_max_rss: u64,
+    actix_cpu_p50: f32,
+    actix_max_rss: u64,
+}
+
+impl From<&Results> for ResultRow {
+    fn from(r: &Results) -> Self {
+        ResultRow {
+            target: r.name.clone(),
+            test: r.test.clone(),
+            concurrency: r.concurrency,
+            profile: r.profile.clone(),
+            p50_ms: r.stats.p50,
+            p90_ms: r.stats.p90,
+            p99_ms: r.stats.p99,
+            p999_ms: r.stats.p999,
+            max_ms: r.stats.max,
+            rps: r.stats.rps,
+            postgres_cpu_p50: r.proc_stats.postgres.cpu_p50,
+            postgres_max_rss: r.proc_stats.postgres.max_rss,
+            node_cpu_p50: r.proc_stats.node.cpu_p50,
+            node_max_rss: r.proc_stats.node.max_rss,
+            actix_cpu_p50: r.proc_stats.actix.cpu_p50,
+            actix_max_rss: r.proc_stats.actix.max_rss,
+        }
+    }
+}
+
+/// Streams completed `Results` rows out as they arrive, so a long sweep can
+/// be tailed live (NDJSON/CSV) instead of only dumped at the very end.
+pub struct Writer {
+    format: Format,
+    sink: Box<dyn Write + Send>,
+    meta: RunMetadata,
+    rows: Vec<ResultRow>,
+    wrote_csv_header: bool,
+}
+
+impl Writer {
+    pub fn new(format: Format, path: Option<&str>, meta: RunMetadata) -> anyhow::Result<Self> {
+        let mut sink: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(File::create(path)?),
+            None => Box::new(stdout()),
+        };
+
+        match format {
+            // Streamed formats emit the metadata up front; Json wraps it
+            // around the buffered results instead, in `finish()`.
+            Format::Ndjson => {
+                serde_json::to_writer(&mut sink, &meta)?;
+                writeln!(sink)?;
+            }
+            Format::Csv => writeln!(sink, "# {}", serde_json::to_string(&meta)?)?,
+            Format::Json => {}
+        }
+
+        Ok(Writer { format, sink, meta, rows: Vec::new(), wrote_csv_header: false })
+    }
+
+    pub fn write_result(&mut self, result: &Results) -> anyhow::Result<()> {
+        let row = ResultRow::from(result);
+        match self.format {
+            // Buffered so the final array can be wrapped in one `{meta, results}` object.
+            Format::Json => self.rows.push(row),
+            Format::Ndjson => {
+                serde_json::to_writer(&mut self.sink, &row)?;
+                writeln!(self.sink)?;
+            }
+            Format::Csv => {
+                if !self.wrote_csv_header {
+                    writeln!(
+                        self.sink,
+                        "target,test,concurrency,profile,p50_ms,p90_ms,p99_ms,p999_ms,max_ms,rps,\
+                         postgres_cpu_p50,postgres_max_rss,node_cpu_p50,node_max_rss,actix_cpu_p50,actix_max_rss"
+                    )?;
+                    self.wrote_csv_header = true;
+                }
+                writeln!(
+                    self.sink,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    row.target, row.test, row.concurrency, row.profile,
+                    row.p50_ms, row.p90_ms, row.p99_ms, row.p999_ms, row.max_ms, row.rps,
+                    row.postgres_cpu_p50, row.postgres_max_rss,
+                    row.node_cpu_p50, row.node_max_rss,
+                    row.actix_cpu_p50, row.actix_max_rss,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<()> {
+        if let Format::Json = self.format {
+            #[derive(Serialize)]
+            struct Document<'a> {
+                meta: &'a RunMetadata,
+                results: &'a [ResultRow],
+            }
+            serde_json::to_writer_pretty(&mut self.sink, &Document { meta: &self.meta, results: &self.rows })?;
+            writeln!(self.sink)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use futures::{SinkExt, StreamExt};
+use hdrhistogram::Histogram;
+use tokio::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::loadgen::{LatencyStats, MAX_LATENCY_MICROS};
+
+fn micros_to_ms(v: u64) -> f32 {
+    v as f32 / 1000f32
+}
+
+/// Drives `url` with `concurrency` persistent WebSocket connections for
+/// `duration`, each sending a `payload_size` byte frame, awaiting the echoed
+/// response, and recording the round-trip into the same histogram-backed
+/// stats as the HTTP load path. A client reconnects after `max_payload`
+/// bytes sent, if set, to exercise reconnection under the same workload.
+pub async fn run(
+    url: String,
+    concurrency: u16,
+    duration: Duration,
+    payload_size: usize,
+    max_payload: Option<u64>,
+) -> anyhow::Result<LatencyStats> {
+    let histogram: Arc<Mutex<Histogram<u64>>> = Arc::new(Mutex::new(Histogram::new_with_bounds(
+        1,
+        MAX_LATENCY_MICROS,
+        3,
+    )?));
+    let deadline = Instant::now() + duration;
+    let completed = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let payload = vec![0u8; payload_size];
+
+    let mut workers = Vec::with_capacity(concurrency as usize);
+    for _ in 0..concurrency {
+        let url = url.clone();
+        let payload = payload.clone();
+        let histogram = histogram.clone();
+        let completed = completed.clone();
+        workers.push(tokio::spawn(async move {
+            let mut sent_bytes = 0u64;
+            // A worker that can't connect contributes nothing rather than
+            // aborting the whole sweep, matching the HTTP path swallowing
+            // per-request errors.
+            let mut ws = match tokio_tungstenite::connect_async(&url).await {
+                Ok((ws, _)) => ws,
+                Err(_) => return,
+            };
+
+            while Instant::now() < deadline {
+                let start = Instant::now();
+                if ws.send(Message::Binary(payload.clone())).await.is_err() {
+                    break;
+                }
+
+                match ws.next().await {
+                    // Only a real echoed data frame counts as a completed
+                    // round-trip; a Ping/Pong/Close, a protocol error, or a
+                    // dropped stream isn't a successful message.
+                    Some(Ok(Message::Binary(_))) | Some(Ok(Message::Text(_))) => {
+                        let elapsed_us = start.elapsed().as_micros() as u64;
+                        let clamped = elapsed_us.max(1).min(MAX_LATENCY_MICROS);
+                        histogram.lock().unwrap().record(clamped).ok();
+                        completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    }
+                    _ => {}
+                }
+
+                sent_bytes += payload.len() as u64;
+                if let Some(max_payload) = max_payload {
+                    if sent_bytes >= max_payload {
+                        match tokio_tungstenite::connect_async(&url).await {
+                            Ok((new_ws, _)) => {
+                                ws = new_ws;
+                                sent_bytes = 0;
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                }
+            }
+        }));
+    }
+
+    let start = Instant::now();
+    for worker in workers {
+        worker.await?;
+    }
+    let elapsed = start.elapsed().as_secs_f32().max(f32::EPSILON);
+
+    let histogram = histogram.lock().unwrap();
+    Ok(LatencyStats {
+        p50: micros_to_ms(histogram.value_at_quantile(0.50)),
+        p90: micros_to_ms(histogram.value_at_quantile(0.90)),
+        p99: micros_to_ms(histogram.value_at_quantile(0.99)),
+        p999: micros_to_ms(histogram.value_at_quantile(0.999)),
+        max: micros_to_ms(histogram.max()),
+        rps: (completed.load(std::sync::atomic::Ordering::Relaxed) as f32 / elapsed) as usize,
+    })
+}
@@ -1,11 +1,18 @@
 use std::io;
 
-use futures::FutureExt;
-
 use super::{PgConnection, Task};
 use actix::{Handler, Message, ResponseFuture};
 use serde::Deserialize;
 
+const TASKS: &str = "select id, summary, description, assignee_id, assignee_name from tasks";
+const TASKS_SUMMARY: &str = "select id, summary, description, assignee_id, assignee_name from tasks where summary like $1";
+const TASKS_NAME: &str = "select id, summary, description, assignee_id, assignee_name from tasks where assignee_name like $1";
+const TASKS_NAME_SUMMARY: &str = "select id, summary, description, assignee_id, assignee_name from tasks where summary like $1 and assignee_name like $2";
+
+fn as_io_err<E: std::fmt::Debug>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, format!("{:?}", e))
+}
+
 #[derive(Deserialize)]
 pub struct GetTasks {
     summary: Option<String>,
@@ -22,36 +29,32 @@ impl Handler<GetTasks> for PgConnection {
     fn handle(
         &mut self, GetTasks { summary, assignee_name }: GetTasks, _: &mut Self::Context,
     ) -> Self::Result {
-		let cl = self.client();
-		let like = |s| format!("%{}%", s);
-        /*let st = if summary.is_some() && assignee_name.is_some() {
-            cl.tasks_name_summary
-        } else if summary.is_some() {
-            cl.tasks_summary
-        } else if assignee_name.is_some() {
-            cl.tasks_name
-        } else {
-            cl.tasks
-        };*/
+        let pool = self.pool.clone();
+        let like = |s| format!("%{}%", s);
+
         let query = async move {
-            if summary.is_some() && assignee_name.is_some() {
+            let cl = pool.get().await.map_err(as_io_err)?;
+
+            let rows = if summary.is_some() && assignee_name.is_some() {
                 let summary = like(summary.unwrap());
                 let assignee_name = like(assignee_name.unwrap());
-                cl.conn.query(&cl.tasks_name_summary, &[&summary, &assignee_name]).await
+                let st = cl.prepare_cached(TASKS_NAME_SUMMARY).await.map_err(as_io_err)?;
+                cl.query(&st, &[&summary, &assignee_name]).await
             } else if summary.is_some() {
                 let summary = like(summary.unwrap());
-                cl.conn.query(&cl.tasks_summary, &[&summary]).await
+                let st = cl.prepare_cached(TASKS_SUMMARY).await.map_err(as_io_err)?;
+                cl.query(&st, &[&summary]).await
             } else if assignee_name.is_some() {
                 let assignee_name = like(assignee_name.unwrap());
-                cl.conn.query(&cl.tasks_name, &[&assignee_name]).await
+                let st = cl.prepare_cached(TASKS_NAME).await.map_err(as_io_err)?;
+                cl.query(&st, &[&assignee_name]).await
             } else {
-                cl.conn.query(&cl.tasks, &[]).await
+                let st = cl.prepare_cached(TASKS).await.map_err(as_io_err)?;
+                cl.query(&st, &[]).await
             }
-        };
+            .map_err(as_io_err)?;
 
-        let get_tasks = query.map(|res| match res {
-            Err(e) => Err(io::Error::new(io::ErrorKind::Other, format!("{:?}", e))),
-            Ok(rows) => Ok(rows
+            Ok(rows
                 .iter()
                 .map(|row| Task {
                     id: row.get(0),
@@ -60,8 +63,8 @@ impl Handler<GetTasks> for PgConnection {
                     assignee_id: row.get(3),
                     assignee_name: row.get(4),
                 })
-                .collect()),
-        });
-        Box::pin(get_tasks)
+                .collect())
+        };
+        Box::pin(query)
     }
 }
@@ -0,0 +1,50 @@
+use actix::{Actor, Context};
+use deadpool_postgres::{ManagerConfig, Pool, RecyclingMethod};
+use serde::Serialize;
+use tokio_pg_mapper::PostgresMapper;
+use tokio_postgres::NoTls;
+
+mod get_tasks;
+pub use get_tasks::GetTasks;
+
+#[derive(Serialize, PostgresMapper)]
+#[pg_mapper(table = "task")]
+pub struct Task {
+    pub id: i32,
+    pub summary: String,
+    pub description: Option<String>,
+    pub assignee_id: i32,
+    pub assignee_name: String,
+}
+
+/// Actix actor wrapping a pooled Postgres connection.
+///
+/// Previously this held a single long-lived `tokio_postgres` client, which
+/// serialized every `GetTasks` lookup through one socket. It now hands out
+/// a pooled connection per `handle()` call instead, so concurrent lookups
+/// fan out across the pool rather than queuing behind each other.
+pub struct PgConnection {
+    pool: Pool,
+}
+
+impl PgConnection {
+    pub fn new(pool: Pool) -> Self {
+        PgConnection { pool }
+    }
+
+    /// Builds the pool used by `PgConnection`, with `Verified` recycling so a
+    /// backend that died while checked out (e.g. Postgres restart) gets
+    /// probed with a cheap query and replaced instead of handed back out.
+    pub fn build_pool(pg_config: tokio_postgres::Config, size: usize) -> Result<Pool, deadpool_postgres::BuildError> {
+        let manager = deadpool_postgres::Manager::from_config(
+            pg_config,
+            NoTls,
+            ManagerConfig { recycling_method: RecyclingMethod::Verified },
+        );
+        Pool::builder(manager).max_size(size).build()
+    }
+}
+
+impl Actor for PgConnection {
+    type Context = Context<Self>;
+}